@@ -0,0 +1,261 @@
+//! A Chase-Lev style work-stealing deque.
+//!
+//! Each deque has a single owning [`Worker`] that pushes and pops from the
+//! bottom (LIFO order, no contention with other workers) and any number of
+//! [`Stealer`] handles that steal from the top using a CAS loop. The
+//! underlying ring buffer grows by doubling when the owner runs out of room.
+
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicIsize, AtomicPtr, Ordering};
+use std::sync::Arc;
+
+const MIN_CAP: usize = 32;
+
+/// How many retired (grown-past) buffers `Worker::grow` keeps around before
+/// actually freeing the oldest one. There's no epoch/hazard-pointer scheme
+/// here to know precisely when a stealer that raced a given grow has
+/// finished its single read, so freeing is delayed by this many subsequent
+/// grow cycles instead of happening immediately; see the comment in `grow`.
+const RETIRE_LIMIT: usize = 4;
+
+struct Buffer<T> {
+    ptr: *mut MaybeUninit<T>,
+    cap: usize,
+}
+
+impl<T> Buffer<T> {
+    fn alloc(cap: usize) -> Self {
+        let mut v: Vec<MaybeUninit<T>> = Vec::with_capacity(cap);
+        let ptr = v.as_mut_ptr();
+        std::mem::forget(v);
+        Buffer { ptr, cap }
+    }
+
+    unsafe fn dealloc(self) {
+        drop(Vec::from_raw_parts(self.ptr, 0, self.cap));
+    }
+
+    fn mask(&self) -> isize {
+        self.cap as isize - 1
+    }
+
+    unsafe fn write(&self, index: isize, value: T) {
+        ptr::write(self.ptr.offset(index & self.mask()).cast(), value);
+    }
+
+    unsafe fn read(&self, index: isize) -> T {
+        ptr::read(self.ptr.offset(index & self.mask()).cast())
+    }
+}
+
+struct Inner<T> {
+    bottom: AtomicIsize,
+    top: AtomicIsize,
+    buffer: AtomicPtr<Buffer<T>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let bottom = self.bottom.load(Ordering::Relaxed);
+        let mut top = self.top.load(Ordering::Relaxed);
+        let buffer = unsafe { Box::from_raw(self.buffer.load(Ordering::Relaxed)) };
+
+        while top != bottom {
+            unsafe {
+                drop(buffer.read(top));
+            }
+            top = top.wrapping_add(1);
+        }
+
+        unsafe { buffer.dealloc() };
+    }
+}
+
+/// The single-owner half of a deque. Not `Sync`: only the owning worker
+/// thread may push or pop.
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+    /// Buffers retired by `grow`, kept alive for a bounded trailing window
+    /// instead of freed immediately. Only ever touched by the owning
+    /// thread, like everything else on `Worker`.
+    garbage: RefCell<Vec<*mut Buffer<T>>>,
+    _marker: PhantomData<Cell<()>>,
+}
+
+impl<T> Drop for Worker<T> {
+    fn drop(&mut self) {
+        for ptr in self.garbage.borrow_mut().drain(..) {
+            let buffer = unsafe { Box::from_raw(ptr) };
+            unsafe { buffer.dealloc() };
+        }
+    }
+}
+
+/// A cloneable handle that steals from the top of the deque.
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+// `Inner<T>`'s only `T`-bearing field is an `AtomicPtr<Buffer<T>>`, which is
+// unconditionally `Send`/`Sync` regardless of `T`, so these bounds don't
+// auto-derive from the field types and must be asserted explicitly: moving
+// or sharing a `T` across threads is only sound when `T: Send`.
+unsafe impl<T: Send> Send for Worker<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+/// Creates a new empty work-stealing deque, returning the owning `Worker`
+/// and a `Stealer` that can be cloned and handed to other threads.
+pub fn worker<T>() -> (Worker<T>, Stealer<T>) {
+    let buffer = Box::into_raw(Box::new(Buffer::alloc(MIN_CAP)));
+    let inner = Arc::new(Inner {
+        bottom: AtomicIsize::new(0),
+        top: AtomicIsize::new(0),
+        buffer: AtomicPtr::new(buffer),
+    });
+
+    (
+        Worker {
+            inner: inner.clone(),
+            garbage: RefCell::new(Vec::new()),
+            _marker: PhantomData,
+        },
+        Stealer { inner },
+    )
+}
+
+impl<T> Worker<T> {
+    /// Pushes an item onto the bottom of the deque, growing the backing
+    /// buffer if it's full.
+    pub fn push(&self, value: T) {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed);
+        let top = self.inner.top.load(Ordering::Acquire);
+
+        let mut buffer = unsafe { &*self.inner.buffer.load(Ordering::Relaxed) };
+        if bottom.wrapping_sub(top) >= buffer.cap as isize {
+            buffer = self.grow(buffer, bottom, top);
+        }
+
+        unsafe { buffer.write(bottom, value) };
+        self.inner.bottom.store(bottom + 1, Ordering::Release);
+    }
+
+    fn grow<'a>(&'a self, buffer: &Buffer<T>, bottom: isize, top: isize) -> &'a Buffer<T> {
+        let new_buffer = Box::new(Buffer::alloc(buffer.cap * 2));
+        for i in top..bottom {
+            unsafe { new_buffer.write(i, buffer.read(i)) };
+        }
+
+        let new_ptr = Box::into_raw(new_buffer);
+        let old_ptr = self.inner.buffer.swap(new_ptr, Ordering::Release);
+
+        // `old_ptr` can't be freed immediately: a stealer may have already
+        // loaded it (Acquire'd before this swap) and be mid-`read` from it.
+        // Retire it into a bounded trailing window instead and only free
+        // the oldest entry once RETIRE_LIMIT further grow cycles have
+        // passed, by which point any steal racing the swap that retired it
+        // has long since finished its single read + CAS. This bounds the
+        // leak to RETIRE_LIMIT buffers instead of leaking one every grow
+        // for the life of the pool.
+        let mut garbage = self.garbage.borrow_mut();
+        garbage.push(old_ptr);
+        if garbage.len() > RETIRE_LIMIT {
+            let oldest = unsafe { Box::from_raw(garbage.remove(0)) };
+            unsafe { oldest.dealloc() };
+        }
+
+        unsafe { &*new_ptr }
+    }
+
+    /// Returns `true` if the deque currently holds no items, from the
+    /// owning worker's point of view. A concurrent steal can make this
+    /// stale the instant it returns, so it's only meant for a shutdown
+    /// drain loop checked from the owning thread, not synchronization.
+    pub fn is_empty(&self) -> bool {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed);
+        let top = self.inner.top.load(Ordering::Acquire);
+        top >= bottom
+    }
+
+    /// Pops from the bottom of the deque (LIFO). Returns `None` if empty.
+    pub fn pop(&self) -> Option<T> {
+        let bottom = self.inner.bottom.load(Ordering::Relaxed) - 1;
+        let buffer = unsafe { &*self.inner.buffer.load(Ordering::Relaxed) };
+        self.inner.bottom.store(bottom, Ordering::SeqCst);
+
+        let top = self.inner.top.load(Ordering::SeqCst);
+
+        if top > bottom {
+            // Deque was already empty; restore bottom.
+            self.inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            return None;
+        }
+
+        let mut value = Some(unsafe { buffer.read(bottom) });
+
+        if top == bottom {
+            // Last element: race a stealer for it via CAS on `top`.
+            if self
+                .inner
+                .top
+                .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_err()
+            {
+                // Lost the race: the stealer that won the CAS has its own
+                // bit-copy of this same slot and will drop/return it, so
+                // forget ours instead of dropping it here too (the read
+                // above was a bitwise copy, not a move out of the buffer).
+                std::mem::forget(value.take());
+            }
+            self.inner.bottom.store(bottom + 1, Ordering::Relaxed);
+        }
+
+        value
+    }
+}
+
+impl<T> Stealer<T> {
+    /// Attempts to steal one item from the top of the deque.
+    pub fn steal(&self) -> Option<T> {
+        let top = self.inner.top.load(Ordering::Acquire);
+        atomic_fence();
+        let bottom = self.inner.bottom.load(Ordering::Acquire);
+
+        if top >= bottom {
+            return None;
+        }
+
+        let buffer = unsafe { &*self.inner.buffer.load(Ordering::Acquire) };
+        let value = unsafe { buffer.read(top) };
+
+        match self
+            .inner
+            .top
+            .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+        {
+            Ok(_) => Some(value),
+            Err(_) => {
+                // Lost the race with another stealer (or the owner's pop);
+                // the value we read may have already been overwritten, so
+                // drop our logical claim on it rather than returning it.
+                std::mem::forget(value);
+                None
+            }
+        }
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+fn atomic_fence() {
+    std::sync::atomic::fence(Ordering::SeqCst);
+}
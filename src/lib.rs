@@ -1,15 +1,44 @@
+mod deque;
 mod queue;
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, mpsc, Arc, Mutex},
     thread,
+    time::Duration,
 };
+use rand::Rng;
 use crate::queue::ArrayQueue;
 
+/// Snapshot of a pool's health, returned by `ThreadPool::stats` /
+/// `LockFreeThreadPool::stats`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PoolStats {
+    /// Number of jobs whose panic was caught and isolated to a single
+    /// worker rather than taking the worker down.
+    pub panics: usize,
+}
+
+/// Logs a job panic with the id of the worker that caught it, extracting
+/// a human-readable message from the common payload types.
+fn log_job_panic(worker_id: usize, payload: &(dyn std::any::Any + Send)) {
+    let message = if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "non-string panic payload".to_string()
+    };
+    eprintln!("Worker {worker_id} panicked while running a job: {message}");
+}
+
 
 // Lock-based ThreadPool implementation
 pub struct ThreadPool {
     workers: Vec<Worker>,
     sender: Option<mpsc::Sender<Job>>,
+    receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+    panic_count: Arc<AtomicUsize>,
 }
 
 impl ThreadPool {
@@ -17,34 +46,76 @@ impl ThreadPool {
         assert!(size > 0);
         let (sender, receiver) = mpsc::channel();
         let receiver = Arc::new(Mutex::new(receiver));
+        let panic_count = Arc::new(AtomicUsize::new(0));
         let mut workers = Vec::with_capacity(size);
-        
+
         for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
+            workers.push(Worker::new(id, Arc::clone(&receiver), Arc::clone(&panic_count)));
         }
-        
+
         ThreadPool {
             workers,
             sender: Some(sender),
+            receiver,
+            panic_count,
         }
     }
 
-    pub fn execute<F>(&self, f: F)
+    /// Submits a job to the pool. The error type is `()` rather than a richer
+    /// error because the only failure mode is the channel's receiver having
+    /// been dropped, which can't happen while `self` is alive.
+    #[allow(clippy::result_unit_err)]
+    pub fn execute<F>(&mut self, f: F) -> Result<(), ()>
     where
         F: FnOnce() + Send + 'static,
     {
+        self.respawn_dead_workers();
         let job = Box::new(f);
-        self.sender.as_ref().unwrap().send(job).unwrap();
+        self.sender.as_ref().unwrap().send(job).map_err(|_| ())
+    }
+
+    /// Replaces any worker whose thread has already exited (normally only
+    /// a panic that escaped `catch_unwind`) so the pool keeps exactly
+    /// `workers.len()` threads alive.
+    fn respawn_dead_workers(&mut self) {
+        for worker in &mut self.workers {
+            let dead = worker.thread.as_ref().is_some_and(|t| t.is_finished());
+            if dead {
+                if let Some(thread) = worker.thread.take() {
+                    let _ = thread.join();
+                }
+                println!("Respawning worker {} after unexpected exit", worker.id);
+                *worker = Worker::new(worker.id, Arc::clone(&self.receiver), Arc::clone(&self.panic_count));
+            }
+        }
+    }
+
+    /// Returns a snapshot of how many job panics this pool has caught.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            panics: self.panic_count.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Submits a blocking job (file I/O, `thread::sleep`, etc.) to this
+    /// pool. Functionally identical to `execute`; the distinct name lets a
+    /// pool instance dedicated to blocking work read as such at call sites.
+    #[allow(clippy::result_unit_err)]
+    pub fn spawn_blocking<F>(&mut self, f: F) -> Result<(), ()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.execute(f)
     }
 }
 
 impl Drop for ThreadPool {
     fn drop(&mut self) {
         drop(self.sender.take());
-        
+
         for worker in &mut self.workers {
             println!("Shutting down worker {}", worker.id);
-            
+
             if let Some(thread) = worker.thread.take() {
                 thread.join().unwrap();
             }
@@ -60,14 +131,17 @@ struct Worker {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>, panic_count: Arc<AtomicUsize>) -> Worker {
         let thread = thread::spawn(move || loop {
             let message = receiver.lock().unwrap().recv();
-            
+
             match message {
                 Ok(job) => {
                     println!("Worker {id} got a job; executing.");
-                    job();
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                        panic_count.fetch_add(1, Ordering::Relaxed);
+                        log_job_panic(id, &*payload);
+                    }
                 }
                 Err(_) => {
                     println!("Worker {id} disconnected; shutting down.");
@@ -75,10 +149,10 @@ impl Worker {
                 }
             }
         });
-        
-        Worker { 
-            id, 
-            thread: Some(thread) 
+
+        Worker {
+            id,
+            thread: Some(thread)
         }
     }
 }
@@ -89,6 +163,8 @@ pub struct LockFreeThreadPool {
     workers: Vec<LockFreeWorker>,
     job_queue: Arc<ArrayQueue<LockFreeJob>>,
     running: Arc<std::sync::atomic::AtomicBool>,
+    next_unpark: AtomicUsize,
+    panic_count: Arc<AtomicUsize>,
 }
 
 type LockFreeJob = Box<dyn FnOnce() + Send + 'static>;
@@ -98,49 +174,136 @@ struct LockFreeWorker {
     thread: Option<thread::JoinHandle<()>>,
 }
 
+/// Exponential spin-then-park backoff for idle workers. `spin()` doubles
+/// the number of `spin_loop` hints on each empty poll, up to `SPIN_LIMIT`
+/// steps, after which the caller should `park` instead of keep spinning.
+struct Backoff {
+    step: Cell<u32>,
+}
+
+const SPIN_LIMIT: u32 = 6;
+
+impl Backoff {
+    fn new() -> Self {
+        Backoff { step: Cell::new(0) }
+    }
+
+    fn reset(&self) {
+        self.step.set(0);
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.step.get() > SPIN_LIMIT
+    }
+
+    fn spin(&self) {
+        let step = self.step.get();
+        for _ in 0..(1u32 << step.min(SPIN_LIMIT)) {
+            std::hint::spin_loop();
+        }
+        if step <= SPIN_LIMIT {
+            self.step.set(step + 1);
+        }
+    }
+}
+
 impl LockFreeThreadPool {
     pub fn new(size: usize, queue_capacity: usize) -> LockFreeThreadPool {
         assert!(size > 0);
         assert!(queue_capacity > 0);
-        
+
         let job_queue = Arc::new(ArrayQueue::new(queue_capacity));
         let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let panic_count = Arc::new(AtomicUsize::new(0));
         let mut workers = Vec::with_capacity(size);
-        
+
         for id in 0..size {
             workers.push(LockFreeWorker::new(
                 id,
                 Arc::clone(&job_queue),
-                Arc::clone(&running)
+                Arc::clone(&running),
+                Arc::clone(&panic_count),
             ));
         }
-        
+
         LockFreeThreadPool {
             workers,
             job_queue,
             running,
+            next_unpark: AtomicUsize::new(0),
+            panic_count,
         }
     }
 
-    pub fn execute<F>(&self, f: F) -> Result<(), ()>
+    /// Submits a job to the pool. The error type is `()` rather than a
+    /// richer error, matching `WorkStealingPool::execute`: the only failure
+    /// mode is the bounded job queue being full.
+    #[allow(clippy::result_unit_err)]
+    pub fn execute<F>(&mut self, f: F) -> Result<(), ()>
     where
         F: FnOnce() + Send + 'static,
     {
+        self.respawn_dead_workers();
         let job = Box::new(f);
         match self.job_queue.push(job) {
-            Ok(()) => Ok(()),
+            Ok(()) => {
+                // Wake one worker; if it was already running this is a
+                // harmless no-op unpark.
+                let idx = self.next_unpark.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+                if let Some(thread) = &self.workers[idx].thread {
+                    thread.thread().unpark();
+                }
+                Ok(())
+            }
             Err(_) => {
                 eprintln!("Queue is full, rejecting job");
                 Err(())
             },
         }
     }
+
+    /// Replaces any worker whose thread has already exited (normally only
+    /// a panic that escaped `catch_unwind`) so the pool keeps exactly
+    /// `workers.len()` threads alive.
+    fn respawn_dead_workers(&mut self) {
+        for worker in &mut self.workers {
+            let dead = worker.thread.as_ref().is_some_and(|t| t.is_finished());
+            if dead {
+                if let Some(thread) = worker.thread.take() {
+                    let _ = thread.join();
+                }
+                println!("Respawning worker {} after unexpected exit", worker.id);
+                *worker = LockFreeWorker::new(
+                    worker.id,
+                    Arc::clone(&self.job_queue),
+                    Arc::clone(&self.running),
+                    Arc::clone(&self.panic_count),
+                );
+            }
+        }
+    }
+
+    /// Returns a snapshot of how many job panics this pool has caught.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            panics: self.panic_count.load(Ordering::Relaxed),
+        }
+    }
 }
 
 impl Drop for LockFreeThreadPool {
     fn drop(&mut self) {
         self.running.store(false, std::sync::atomic::Ordering::SeqCst);
 
+        // Workers may be parked waiting for work; wake them all so they
+        // observe `running == false` instead of sleeping until their
+        // park_timeout fallback expires.
+        for worker in &self.workers {
+            if let Some(thread) = &worker.thread {
+                thread.thread().unpark();
+            }
+        }
+
         for worker in &mut self.workers {
             if let Some(thread) = worker.thread.take() {
                 println!("Shutting down worker {}", worker.id);
@@ -155,25 +318,279 @@ impl LockFreeWorker {
         id: usize,
         job_queue: Arc<ArrayQueue<LockFreeJob>>,
         running: Arc<std::sync::atomic::AtomicBool>,
+        panic_count: Arc<AtomicUsize>,
     ) -> LockFreeWorker {
         let thread = thread::spawn(move || {
-            while running.load(std::sync::atomic::Ordering::SeqCst) {
+            let backoff = Backoff::new();
+
+            // Once `running` flips to false, keep draining until the
+            // injector is empty instead of abandoning queued jobs.
+            while running.load(std::sync::atomic::Ordering::SeqCst) || !job_queue.is_empty() {
                 match job_queue.pop() {
                     Some(job) => {
+                        backoff.reset();
                         println!("Worker {id} got a job; executing.");
-                        job();
+                        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                            panic_count.fetch_add(1, Ordering::Relaxed);
+                            log_job_panic(id, &*payload);
+                        }
+                    }
+                    None if backoff.is_exhausted() => {
+                        // A push that happened just before we parked would
+                        // already have sent us an unpark, but fall back to
+                        // waking up on our own so we never sleep forever.
+                        thread::park_timeout(Duration::from_millis(10));
                     }
                     None => {
-                        thread::yield_now();
+                        backoff.spin();
                     }
                 }
             }
             println!("Worker {id} shutting down.");
         });
-        
+
         LockFreeWorker {
             id,
             thread: Some(thread),
         }
     }
 }
+
+
+
+/// A thread pool where each worker owns a Chase-Lev work-stealing deque,
+/// backed by a shared `ArrayQueue` injector for freshly-submitted jobs.
+///
+/// Compared to `LockFreeThreadPool`, workers only contend on the injector
+/// once their own deque is empty, and then take up to `INJECTOR_BATCH` jobs
+/// at a time; stealing from other workers' deques comes after that, which
+/// keeps throughput high under bursty or unevenly-distributed workloads.
+pub struct WorkStealingPool {
+    workers: Vec<WorkStealingWorker>,
+    injector: Arc<ArrayQueue<LockFreeJob>>,
+    running: Arc<AtomicBool>,
+    stealers: Arc<Mutex<Vec<deque::Stealer<LockFreeJob>>>>,
+    panic_count: Arc<AtomicUsize>,
+    next_unpark: AtomicUsize,
+}
+
+struct WorkStealingWorker {
+    id: usize,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// How many jobs a worker drains from the global injector into its local
+/// deque in one go, once its own deque runs dry.
+const INJECTOR_BATCH: usize = 32;
+
+impl WorkStealingPool {
+    pub fn new(size: usize, queue_capacity: usize) -> WorkStealingPool {
+        assert!(size > 0);
+        assert!(queue_capacity > 0);
+
+        let injector = Arc::new(ArrayQueue::new(queue_capacity));
+        let running = Arc::new(AtomicBool::new(true));
+        let panic_count = Arc::new(AtomicUsize::new(0));
+
+        let mut locals = Vec::with_capacity(size);
+        let mut stealers = Vec::with_capacity(size);
+        for _ in 0..size {
+            let (local, stealer) = deque::worker();
+            locals.push(local);
+            stealers.push(stealer);
+        }
+        let stealers = Arc::new(Mutex::new(stealers));
+
+        let workers = locals
+            .into_iter()
+            .enumerate()
+            .map(|(id, local)| {
+                WorkStealingWorker::new(
+                    id,
+                    local,
+                    Arc::clone(&stealers),
+                    Arc::clone(&injector),
+                    Arc::clone(&running),
+                    Arc::clone(&panic_count),
+                )
+            })
+            .collect();
+
+        WorkStealingPool {
+            workers,
+            injector,
+            running,
+            stealers,
+            panic_count,
+            next_unpark: AtomicUsize::new(0),
+        }
+    }
+
+    /// Submits a job to the pool. The error type is `()` rather than a
+    /// richer error, matching `LockFreeThreadPool::execute`: the only
+    /// failure mode is the injector being full.
+    #[allow(clippy::result_unit_err)]
+    pub fn execute<F>(&mut self, f: F) -> Result<(), ()>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.respawn_dead_workers();
+        let job = Box::new(f);
+        match self.injector.push(job) {
+            Ok(()) => {
+                // Wake one worker in case it's parked after exhausting its
+                // backoff; a harmless no-op unpark if it was already awake.
+                let idx = self.next_unpark.fetch_add(1, Ordering::Relaxed) % self.workers.len();
+                if let Some(thread) = &self.workers[idx].thread {
+                    thread.thread().unpark();
+                }
+                Ok(())
+            }
+            Err(_) => {
+                eprintln!("Queue is full, rejecting job");
+                Err(())
+            }
+        }
+    }
+
+    /// Replaces any worker whose thread has already exited (normally only
+    /// a panic that escaped `catch_unwind`) so the pool keeps exactly
+    /// `workers.len()` threads alive. The replacement gets a fresh, empty
+    /// local deque; its stealer handle is swapped into `stealers` under the
+    /// same index so other workers keep stealing from the right slot.
+    fn respawn_dead_workers(&mut self) {
+        for worker in &mut self.workers {
+            let dead = worker.thread.as_ref().is_some_and(|t| t.is_finished());
+            if dead {
+                if let Some(thread) = worker.thread.take() {
+                    let _ = thread.join();
+                }
+                println!("Respawning worker {} after unexpected exit", worker.id);
+
+                let (local, stealer) = deque::worker();
+                self.stealers.lock().unwrap()[worker.id] = stealer;
+                *worker = WorkStealingWorker::new(
+                    worker.id,
+                    local,
+                    Arc::clone(&self.stealers),
+                    Arc::clone(&self.injector),
+                    Arc::clone(&self.running),
+                    Arc::clone(&self.panic_count),
+                );
+            }
+        }
+    }
+
+    /// Returns a snapshot of how many job panics this pool has caught.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            panics: self.panic_count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for WorkStealingPool {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+
+        // Workers may be parked waiting for work; wake them all so they
+        // observe `running == false` instead of sleeping until their
+        // park_timeout fallback expires.
+        for worker in &self.workers {
+            if let Some(thread) = &worker.thread {
+                thread.thread().unpark();
+            }
+        }
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                println!("Shutting down worker {}", worker.id);
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+impl WorkStealingWorker {
+    fn new(
+        id: usize,
+        local: deque::Worker<LockFreeJob>,
+        stealers: Arc<Mutex<Vec<deque::Stealer<LockFreeJob>>>>,
+        injector: Arc<ArrayQueue<LockFreeJob>>,
+        running: Arc<AtomicBool>,
+        panic_count: Arc<AtomicUsize>,
+    ) -> WorkStealingWorker {
+        let thread = thread::spawn(move || {
+            let mut rng = rand::thread_rng();
+            let backoff = Backoff::new();
+
+            // Once `running` flips to false, keep draining this worker's own
+            // local deque and the shared injector instead of abandoning
+            // queued jobs; other workers do the same for their own deques.
+            while running.load(Ordering::SeqCst) || !local.is_empty() || !injector.is_empty() {
+                if let Some(job) = local.pop() {
+                    backoff.reset();
+                    println!("Worker {id} got a job; executing.");
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                        panic_count.fetch_add(1, Ordering::Relaxed);
+                        log_job_panic(id, &*payload);
+                    }
+                    continue;
+                }
+
+                let mut refilled = false;
+                for _ in 0..INJECTOR_BATCH {
+                    match injector.pop() {
+                        Some(job) => {
+                            local.push(job);
+                            refilled = true;
+                        }
+                        None => break,
+                    }
+                }
+                if refilled {
+                    backoff.reset();
+                    continue;
+                }
+
+                let stolen = {
+                    let stealers = stealers.lock().unwrap();
+                    if stealers.len() > 1 {
+                        let victim = rng.gen_range(0..stealers.len());
+                        if victim != id {
+                            stealers[victim].steal().map(|job| (victim, job))
+                        } else {
+                            None
+                        }
+                    } else {
+                        None
+                    }
+                };
+                if let Some((victim, job)) = stolen {
+                    backoff.reset();
+                    println!("Worker {id} stole a job from worker {victim}; executing.");
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                        panic_count.fetch_add(1, Ordering::Relaxed);
+                        log_job_panic(id, &*payload);
+                    }
+                    continue;
+                }
+
+                if backoff.is_exhausted() {
+                    // A push that happened just before we parked would
+                    // already have sent us an unpark, but fall back to
+                    // waking up on our own so we never sleep forever.
+                    thread::park_timeout(Duration::from_millis(10));
+                } else {
+                    backoff.spin();
+                }
+            }
+            println!("Worker {id} shutting down.");
+        });
+
+        WorkStealingWorker {
+            id,
+            thread: Some(thread),
+        }
+    }
+}
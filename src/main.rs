@@ -1,20 +1,70 @@
+mod reactor;
+
 use std::{
     env,
     fs,
-    io::{prelude::*, BufReader},
+    io::{self, prelude::*, BufReader},
     net::{TcpListener, TcpStream},
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex},
     thread,
     time::Duration,
-    
+
 };
-use rand::Rng;  
+use rand::Rng;
+
+use server::{LockFreeThreadPool, ThreadPool, WorkStealingPool};
+
+/// Set by the SIGINT handler; checked by the accept loop and cleared only
+/// at process start. `main` never runs twice, so a plain `static` is fine.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+extern "C" {
+    fn signal(signum: i32, handler: usize) -> usize;
+}
+
+const SIGINT: i32 = 2;
+
+extern "C" fn request_shutdown(_signum: i32) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Installs a Ctrl-C/SIGINT handler that flips `SHUTDOWN` instead of
+/// terminating the process, so `main` can drain in-flight work first.
+fn install_shutdown_handler() {
+    unsafe {
+        signal(SIGINT, request_shutdown as *const () as usize);
+    }
+}
+
+/// Accepts connections until `SHUTDOWN` is set, handing each one to
+/// `on_stream`. The listener is put in non-blocking mode so the loop can
+/// poll the shutdown flag between connections instead of blocking in
+/// `accept()` forever.
+fn run_accept_loop(listener: &TcpListener, mut on_stream: impl FnMut(TcpStream)) {
+    listener
+        .set_nonblocking(true)
+        .expect("failed to set listener non-blocking");
+
+    while !SHUTDOWN.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => on_stream(stream),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => {
+                eprintln!("Failed to accept connection: {e}");
+            }
+        }
+    }
 
-use server::{LockFreeThreadPool, ThreadPool};
+    println!("Shutdown signal received; draining in-flight work.");
+}
 
 fn main() {
-    let mut implementation = "1"; 
-    let mut workers = 8;          
-    let mut queue_size = 100;     
+    let mut implementation = "1";
+    let mut workers = 8;
+    let mut queue_size = 100;
+    let mut blocking_threads = 4;
 
     let args: Vec<String> = env::args().collect();
     let mut i = 1;
@@ -56,16 +106,36 @@ fn main() {
                     return;
                 }
             },
+            "-b" | "--blocking-threads" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<usize>() {
+                        Ok(b) if b > 0 => {
+                            blocking_threads = b;
+                            i += 2;
+                        },
+                        _ => {
+                            println!("Error: Blocking thread count must be a positive number");
+                            return;
+                        }
+                    }
+                } else {
+                    println!("Error: Missing value for blocking threads");
+                    return;
+                }
+            },
             "-h" | "--help" => {
-                println!("Usage: {} [implementation] [-w|--workers N] [-q|--queue-size N]", args[0]);
+                println!("Usage: {} [implementation] [-w|--workers N] [-q|--queue-size N] [-b|--blocking-threads N]", args[0]);
                 println!("Implementations:");
                 println!("  1: Sequential (single-threaded thread pool)");
                 println!("  2: Lock-free queue with thread pool");
                 println!("  3: Lock-based queue with thread pool");
                 println!("  4: Thread-per-connection");
+                println!("  5: Work-stealing thread pool (per-worker deques + global injector)");
+                println!("  6: Non-blocking reactor (epoll readiness loop)");
                 println!("Options:");
-                println!("  -w, --workers N    Number of worker threads (default: 4)");
-                println!("  -q, --queue-size N Size of job queue for lock-free implementation (default: 100)");
+                println!("  -w, --workers N           Number of worker threads (default: 8)");
+                println!("  -q, --queue-size N        Size of job queue for lock-free implementation (default: 100)");
+                println!("  -b, --blocking-threads N  Size of the dedicated blocking pool for /sleep and /mixed I/O (default: 4)");
                 return;
             },
             imp if !imp.starts_with('-') => {
@@ -74,77 +144,128 @@ fn main() {
             },
             _ => {
                 println!("Unknown option: {}", args[i]);
-                println!("Usage: {} [implementation] [-w|--workers N] [-q|--queue-size N]", args[0]);
+                println!("Usage: {} [implementation] [-w|--workers N] [-q|--queue-size N] [-b|--blocking-threads N]", args[0]);
                 return;
             }
         }
     }
 
-    println!("Using implementation {}, {} worker threads, queue size of {}", 
-            &implementation, &workers, &queue_size);
+    println!("Using implementation {}, {} worker threads, queue size of {}, {} blocking threads",
+            &implementation, &workers, &queue_size, &blocking_threads);
 
 
     let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
-    
+    install_shutdown_handler();
+    let blocking_pool = Arc::new(Mutex::new(ThreadPool::new(blocking_threads)));
+
     match implementation {
         "1" => {
             // sequential: single-threaded thread pool
             println!("Running implementation 1: Sequential (single-threaded thread pool)");
-            let pool = ThreadPool::new(1);
-            
-            for stream in listener.incoming() {
-                let stream = stream.unwrap();
-                pool.execute(|| {
-                    handle_connection(stream);
+            let mut pool = ThreadPool::new(1);
+
+            run_accept_loop(&listener, |stream| {
+                let blocking_pool = Arc::clone(&blocking_pool);
+                let _ = pool.execute(move || {
+                    handle_connection(stream, &blocking_pool);
                 });
-            }
+            });
         },
         "2" => {
             // Lock-free queue with thread pool
             println!("Running implementation 2: Lock-free queue with thread pool");
-            let pool = LockFreeThreadPool::new(workers, queue_size);
-            
-            for stream in listener.incoming() {
-                let stream = stream.unwrap();
-                pool.execute(|| {
-                    handle_connection(stream);
+            let mut pool = LockFreeThreadPool::new(workers, queue_size);
+
+            run_accept_loop(&listener, |stream| {
+                let blocking_pool = Arc::clone(&blocking_pool);
+                let _ = pool.execute(move || {
+                    handle_connection(stream, &blocking_pool);
                 });
-            }
+            });
         },
         "3" => {
             // Lock-based queue with thread pool
             println!("Running implementation 3: Lock-based queue with thread pool");
-            let pool = ThreadPool::new(workers);
-            
-            for stream in listener.incoming() {
-                let stream = stream.unwrap();
-                pool.execute(|| {
-                    handle_connection(stream);
+            let mut pool = ThreadPool::new(workers);
+
+            run_accept_loop(&listener, |stream| {
+                let blocking_pool = Arc::clone(&blocking_pool);
+                let _ = pool.execute(move || {
+                    handle_connection(stream, &blocking_pool);
                 });
-            }
+            });
         },
         "4" => {
             // thread-per-connection
             println!("Running implementation 4: thread-per-connection");
-            
-            for stream in listener.incoming() {
-                let stream = stream.unwrap();
-                thread::spawn(|| {
-                    handle_connection(stream);
+
+            run_accept_loop(&listener, |stream| {
+                let blocking_pool = Arc::clone(&blocking_pool);
+                thread::spawn(move || {
+                    handle_connection(stream, &blocking_pool);
                 });
+            });
+        },
+        "5" => {
+            // Work-stealing thread pool
+            println!("Running implementation 5: Work-stealing thread pool");
+            let mut pool = WorkStealingPool::new(workers, queue_size);
+
+            run_accept_loop(&listener, |stream| {
+                let blocking_pool = Arc::clone(&blocking_pool);
+                let _ = pool.execute(move || {
+                    handle_connection(stream, &blocking_pool);
+                });
+            });
+        },
+        "6" => {
+            // Single-threaded non-blocking reactor
+            println!("Running implementation 6: Non-blocking reactor (epoll)");
+            let mut cpu_pool = LockFreeThreadPool::new(workers, queue_size);
+
+            if let Err(e) = reactor::run(listener, &SHUTDOWN, &mut cpu_pool, &blocking_pool) {
+                eprintln!("Reactor exited with error: {e}");
             }
         },
         _ => {
-            println!("Invalid implementation number. Choose 1-4.");
+            println!("Invalid implementation number. Choose 1-6.");
             return;
         }
     }
-    
+
     println!("Shutting down.");
 }
 
-// Function to handle incoming connections
-fn handle_connection(mut stream: TcpStream) {
+/// Runs the 1-second "disk read" simulation and writes the response
+/// directly to `stream`. Meant to run on the dedicated blocking pool so it
+/// never parks a primary worker.
+fn run_sleep_workload(mut stream: TcpStream) {
+    thread::sleep(Duration::from_secs(1));
+    let contents = fs::read_to_string("response.html").unwrap_or_default();
+    write_response(&mut stream, "HTTP/1.1 200 OK", &contents);
+}
+
+/// Same idea as `run_sleep_workload`, but for the I/O branch of `/mixed`.
+fn run_mixed_io_workload(mut stream: TcpStream) {
+    thread::sleep(Duration::from_secs(1));
+    let _ = fs::read_to_string("response.html");
+    write_response(&mut stream, "HTTP/1.1 200 OK", "Mixed workload (I/O): Completed after sleep");
+}
+
+fn write_response(stream: &mut TcpStream, status_line: &str, content: &str) {
+    let length = content.len();
+    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{content}");
+
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        println!("Failed to send response: {}", e);
+    }
+}
+
+/// Handles one incoming connection. `blocking_pool` is shared with every
+/// primary pool implementation and is where `/sleep` and the I/O branch of
+/// `/mixed` get dispatched, so a primary worker is never parked on
+/// `thread::sleep`.
+fn handle_connection(mut stream: TcpStream, blocking_pool: &Arc<Mutex<ThreadPool>>) {
     let buf_reader = BufReader::new(&stream);
     let request_line = match buf_reader.lines().next() {
         Some(Ok(line)) => line,
@@ -160,6 +281,11 @@ fn handle_connection(mut stream: TcpStream) {
 
     println!("Received request: '{}'", request_line);
 
+    if request_line.starts_with("GET /sleep ") {
+        dispatch_to_blocking_pool(stream, blocking_pool, run_sleep_workload);
+        return;
+    }
+
     let (status_line, content) = if request_line.starts_with("GET / ") {
         match fs::read_to_string("response.html") {
             Ok(contents) => ("HTTP/1.1 200 OK", contents),
@@ -177,15 +303,11 @@ fn handle_connection(mut stream: TcpStream) {
         }
         let result = format!("Found {} primes up to 10,000", primes.len());
         ("HTTP/1.1 200 OK", result)
-    } else if request_line.starts_with("GET /sleep ") {
-        thread::sleep(Duration::from_secs(1));  
-        let contents = fs::read_to_string("response.html").unwrap();
-        ("HTTP/1.1 200 OK", contents)
     } else if request_line.starts_with("GET /mixed ") {
-        let mut rng = rand::thread_rng();  
-        let workload_type: u8 = rng.gen_range(0..3);  
-        
-        match workload_type  {
+        let mut rng = rand::thread_rng();
+        let workload_type: u8 = rng.gen_range(0..3);
+
+        match workload_type {
             0 => {
                 // Baseline - quick response
                 let contents = fs::read_to_string("response.html").unwrap();
@@ -203,10 +325,10 @@ fn handle_connection(mut stream: TcpStream) {
                 ("HTTP/1.1 200 OK", result)
             },
             _ => {
-                // IO-bound - sleep
-                thread::sleep(Duration::from_secs(1));
-                let contents = fs::read_to_string("response.html").unwrap();
-                ("HTTP/1.1 200 OK", format!("Mixed workload (I/O): Completed after sleep"))
+                // IO-bound - sleep; hand off to the blocking pool instead
+                // of parking this worker for a full second.
+                dispatch_to_blocking_pool(stream, blocking_pool, run_mixed_io_workload);
+                return;
             }
         }
     } else {
@@ -216,11 +338,24 @@ fn handle_connection(mut stream: TcpStream) {
         ("HTTP/1.1 404 NOT FOUND", contents)
     };
 
-    let length = content.len();
-    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{content}");
+    write_response(&mut stream, status_line, &content);
+}
 
-    if let Err(e) = stream.write_all(response.as_bytes()) {
-        println!("Failed to send response: {}", e);
+/// Submits `workload` to the blocking pool with `stream` captured by
+/// value; on completion the job writes the response straight back to that
+/// stream. `ThreadPool`'s backing channel is unbounded and its receiver is
+/// owned by the pool itself, so `spawn_blocking` does not fail while the
+/// pool is alive; if it ever returned an error, the job (and the captured
+/// `stream`) would be dropped and the client would see the connection
+/// reset rather than a response.
+fn dispatch_to_blocking_pool(
+    stream: TcpStream,
+    blocking_pool: &Arc<Mutex<ThreadPool>>,
+    workload: fn(TcpStream),
+) {
+    let submitted = blocking_pool.lock().unwrap().spawn_blocking(move || workload(stream));
+    if submitted.is_err() {
+        eprintln!("Blocking pool unavailable; dropping connection instead of running workload");
     }
 }
 
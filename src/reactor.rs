@@ -0,0 +1,444 @@
+//! A single-threaded, non-blocking reactor built on a raw `epoll` wrapper.
+//!
+//! Unlike `ThreadPool` / `LockFreeThreadPool`, this implementation never
+//! blocks a thread on socket I/O: the listener and every accepted
+//! `TcpStream` are registered with the OS poller, and a per-connection
+//! state machine tracks how far each request has progressed. CPU-bound
+//! work is handed off to a `LockFreeThreadPool` so it doesn't stall the
+//! event loop; the worker posts its result back through a channel and
+//! wakes the reactor via a self-pipe registered as a normal epoll source.
+
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use rand::Rng;
+use server::{LockFreeThreadPool, ThreadPool};
+
+#[repr(C)]
+#[cfg_attr(target_arch = "x86_64", repr(packed))]
+#[derive(Clone, Copy)]
+struct EpollEvent {
+    events: u32,
+    data: u64,
+}
+
+extern "C" {
+    fn epoll_create1(flags: i32) -> i32;
+    fn epoll_ctl(epfd: i32, op: i32, fd: i32, event: *mut EpollEvent) -> i32;
+    fn epoll_wait(epfd: i32, events: *mut EpollEvent, maxevents: i32, timeout: i32) -> i32;
+    fn close(fd: i32) -> i32;
+    fn pipe(fds: *mut i32) -> i32;
+    fn read(fd: i32, buf: *mut u8, count: usize) -> isize;
+    fn write(fd: i32, buf: *const u8, count: usize) -> isize;
+}
+
+const EPOLL_CTL_ADD: i32 = 1;
+const EPOLL_CTL_DEL: i32 = 2;
+const EPOLL_CTL_MOD: i32 = 3;
+const EPOLLIN: u32 = 0x001;
+const EPOLLOUT: u32 = 0x004;
+
+/// Token reserved for the listening socket.
+const LISTENER_TOKEN: u64 = u64::MAX;
+/// Token reserved for the self-pipe used to wake the reactor when a
+/// CPU-bound job finishes on the background pool.
+const WAKER_TOKEN: u64 = u64::MAX - 1;
+
+enum ConnState {
+    /// Buffering bytes until a full request line (terminated by `\n`) has
+    /// arrived.
+    Reading { buf: Vec<u8> },
+    /// The request line routed to `/cpu`; a job is running on the
+    /// background pool and this connection is deregistered from epoll
+    /// until the result comes back over the waker channel.
+    AwaitingCpuResult,
+    /// Flushing `buf[written..]` to the socket, re-registering for
+    /// writable interest whenever a write would block.
+    Writing { buf: Vec<u8>, written: usize },
+}
+
+struct Connection {
+    stream: TcpStream,
+    state: ConnState,
+}
+
+struct Epoll {
+    fd: RawFd,
+}
+
+impl Epoll {
+    fn new() -> io::Result<Self> {
+        let fd = unsafe { epoll_create1(0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Epoll { fd })
+    }
+
+    fn add(&self, fd: RawFd, interest: u32, token: u64) -> io::Result<()> {
+        self.ctl(EPOLL_CTL_ADD, fd, interest, token)
+    }
+
+    fn modify(&self, fd: RawFd, interest: u32, token: u64) -> io::Result<()> {
+        self.ctl(EPOLL_CTL_MOD, fd, interest, token)
+    }
+
+    fn remove(&self, fd: RawFd) {
+        let mut event = EpollEvent { events: 0, data: 0 };
+        unsafe { epoll_ctl(self.fd, EPOLL_CTL_DEL, fd, &mut event) };
+    }
+
+    fn ctl(&self, op: i32, fd: RawFd, interest: u32, token: u64) -> io::Result<()> {
+        let mut event = EpollEvent {
+            events: interest,
+            data: token,
+        };
+        let ret = unsafe { epoll_ctl(self.fd, op, fd, &mut event) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn wait(&self, events: &mut [EpollEvent], timeout_ms: i32) -> io::Result<usize> {
+        let ret = unsafe { epoll_wait(self.fd, events.as_mut_ptr(), events.len() as i32, timeout_ms) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ret as usize)
+    }
+}
+
+impl Drop for Epoll {
+    fn drop(&mut self) {
+        unsafe { close(self.fd) };
+    }
+}
+
+/// A CPU-bound job's result, matched back to its connection by token.
+struct CpuResult {
+    token: u64,
+    body: String,
+}
+
+/// The plumbing every connection-driving function needs but that doesn't
+/// change per-connection: the poller, the two offload pools, and the
+/// channel/self-pipe used to get CPU results back. Bundled here instead of
+/// threaded through as loose arguments to every function in this module.
+struct ReactorCtx<'a> {
+    epoll: &'a Epoll,
+    cpu_pool: &'a mut LockFreeThreadPool,
+    blocking_pool: &'a Arc<Mutex<ThreadPool>>,
+    result_tx: &'a Sender<CpuResult>,
+    waker_write: RawFd,
+}
+
+/// Runs the reactor until `shutdown` is set. CPU-bound `/cpu` requests and
+/// the CPU branch of `/mixed` go to `cpu_pool`; `/sleep` and the I/O branch
+/// of `/mixed` go to `blocking_pool`, so neither stalls the single reactor
+/// thread. Every other route is handled inline since it completes fast
+/// enough not to.
+pub fn run(
+    listener: TcpListener,
+    shutdown: &AtomicBool,
+    cpu_pool: &mut LockFreeThreadPool,
+    blocking_pool: &Arc<Mutex<ThreadPool>>,
+) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let epoll = Epoll::new()?;
+    epoll.add(listener.as_raw_fd(), EPOLLIN, LISTENER_TOKEN)?;
+
+    let mut waker_fds = [0i32; 2];
+    if unsafe { pipe(waker_fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (waker_read, waker_write) = (waker_fds[0], waker_fds[1]);
+    epoll.add(waker_read, EPOLLIN, WAKER_TOKEN)?;
+
+    let (result_tx, result_rx): (Sender<CpuResult>, Receiver<CpuResult>) = channel();
+    let mut connections: HashMap<u64, Connection> = HashMap::new();
+    let mut events = vec![EpollEvent { events: 0, data: 0 }; 1024];
+    let mut ctx = ReactorCtx {
+        epoll: &epoll,
+        cpu_pool,
+        blocking_pool,
+        result_tx: &result_tx,
+        waker_write,
+    };
+
+    // Once `shutdown` is set, stop accepting new connections but keep
+    // driving every connection already in the map (including ones awaiting
+    // a CPU result) to completion instead of dropping them mid-request.
+    while !shutdown.load(Ordering::SeqCst) || !connections.is_empty() {
+        let n = match epoll.wait(&mut events, 200) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for event in &events[..n] {
+            match event.data {
+                LISTENER_TOKEN => {
+                    if !shutdown.load(Ordering::SeqCst) {
+                        accept_all(&listener, &epoll, &mut connections)?;
+                    }
+                }
+                WAKER_TOKEN => {
+                    drain_waker(waker_read);
+                    while let Ok(result) = result_rx.try_recv() {
+                        complete_cpu_job(&epoll, &mut connections, result);
+                    }
+                }
+                token => drive_connection(&mut ctx, &mut connections, token, event.events),
+            }
+        }
+    }
+
+    unsafe {
+        close(waker_read);
+        close(waker_write);
+    }
+    Ok(())
+}
+
+fn accept_all(listener: &TcpListener, epoll: &Epoll, connections: &mut HashMap<u64, Connection>) -> io::Result<()> {
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(true)?;
+                let token = stream.as_raw_fd() as u64;
+                epoll.add(stream.as_raw_fd(), EPOLLIN, token)?;
+                connections.insert(
+                    token,
+                    Connection {
+                        stream,
+                        state: ConnState::Reading { buf: Vec::new() },
+                    },
+                );
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Consumes wake bytes from the self-pipe. The pipe is in blocking mode, so
+/// this reads only once (epoll has just reported it readable); any bytes
+/// left over keep the level-triggered source ready for the next `wait`.
+fn drain_waker(fd: RawFd) {
+    let mut scratch = [0u8; 64];
+    unsafe { read(fd, scratch.as_mut_ptr(), scratch.len()) };
+}
+
+fn complete_cpu_job(epoll: &Epoll, connections: &mut HashMap<u64, Connection>, result: CpuResult) {
+    let Some(conn) = connections.get_mut(&result.token) else {
+        return;
+    };
+
+    conn.state = ConnState::Writing {
+        buf: response_bytes("HTTP/1.1 200 OK", &result.body),
+        written: 0,
+    };
+    // The stream was removed from epoll when the job was submitted, so it
+    // has to be added back rather than modified.
+    let _ = epoll.add(conn.stream.as_raw_fd(), EPOLLOUT, result.token);
+}
+
+/// Advances the connection identified by `token` in response to the given
+/// epoll readiness bits, closing and deregistering it if the request
+/// completes or the peer disconnects.
+fn drive_connection(
+    ctx: &mut ReactorCtx,
+    connections: &mut HashMap<u64, Connection>,
+    token: u64,
+    readiness: u32,
+) {
+    if readiness & EPOLLIN != 0 && read_ready(ctx, connections, token) {
+        close_connection(ctx.epoll, connections, token);
+        return;
+    }
+
+    if readiness & EPOLLOUT != 0 && write_ready(ctx.epoll, connections, token) {
+        close_connection(ctx.epoll, connections, token);
+    }
+}
+
+/// Returns `true` if the connection should be torn down by the caller.
+/// `/sleep` and the I/O branch of `/mixed` hand the connection's stream
+/// off to `blocking_pool` entirely (removing it from `connections`), so
+/// the caller must not touch it again once this returns `false` for one
+/// of those routes.
+fn read_ready(ctx: &mut ReactorCtx, connections: &mut HashMap<u64, Connection>, token: u64) -> bool {
+    let Some(conn) = connections.get_mut(&token) else {
+        return false;
+    };
+    let ConnState::Reading { buf } = &mut conn.state else {
+        return false;
+    };
+
+    let request_line = match fill_request_line(&mut conn.stream, buf) {
+        Ok(None) => return false,
+        Err(_) => return true,
+        Ok(Some(line)) => line,
+    };
+
+    if request_line.starts_with("GET /cpu ") {
+        if !spawn_cpu_job(ctx, token, "") {
+            return true;
+        }
+        conn.state = ConnState::AwaitingCpuResult;
+        ctx.epoll.remove(conn.stream.as_raw_fd());
+        return false;
+    }
+
+    let mixed_workload = request_line
+        .starts_with("GET /mixed ")
+        .then(|| rand::thread_rng().gen_range(0..3));
+
+    if mixed_workload == Some(1) {
+        if !spawn_cpu_job(ctx, token, "Mixed workload (CPU): ") {
+            return true;
+        }
+        conn.state = ConnState::AwaitingCpuResult;
+        ctx.epoll.remove(conn.stream.as_raw_fd());
+        return false;
+    }
+
+    if request_line.starts_with("GET /sleep ") || mixed_workload == Some(2) {
+        let is_mixed = mixed_workload.is_some();
+        let conn = connections.remove(&token).expect("token just looked up above");
+        ctx.epoll.remove(conn.stream.as_raw_fd());
+        let workload: fn(TcpStream) = if is_mixed {
+            crate::run_mixed_io_workload
+        } else {
+            crate::run_sleep_workload
+        };
+        // `ThreadPool`'s channel is unbounded and the pool owns its receiver,
+        // so this does not fail while the pool is alive; on an error the job
+        // (and the captured stream) would be dropped, resetting the connection.
+        if ctx.blocking_pool.lock().unwrap().spawn_blocking(move || workload(conn.stream)).is_err() {
+            eprintln!("Blocking pool unavailable; dropping reactor request for token {token}");
+        }
+        return false;
+    }
+
+    let Some(conn) = connections.get_mut(&token) else {
+        return false;
+    };
+    let (status, body) = match mixed_workload {
+        Some(_) => match std::fs::read_to_string("response.html") {
+            Ok(contents) => ("HTTP/1.1 200 OK", contents),
+            Err(_) => ("HTTP/1.1 500 Internal Server Error", "Failed to read response.html".to_string()),
+        },
+        None => route(&request_line),
+    };
+    conn.state = ConnState::Writing {
+        buf: response_bytes(status, &body),
+        written: 0,
+    };
+    let _ = ctx.epoll.modify(conn.stream.as_raw_fd(), EPOLLOUT, token);
+    false
+}
+
+/// Returns `true` once the full response has been flushed (or the write
+/// side fails), meaning the connection should be torn down.
+fn write_ready(epoll: &Epoll, connections: &mut HashMap<u64, Connection>, token: u64) -> bool {
+    let Some(conn) = connections.get_mut(&token) else {
+        return false;
+    };
+    let ConnState::Writing { buf, written } = &mut conn.state else {
+        return false;
+    };
+
+    match conn.stream.write(&buf[*written..]) {
+        Ok(0) => true,
+        Ok(n) => {
+            *written += n;
+            *written == buf.len()
+        }
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+            let _ = epoll.modify(conn.stream.as_raw_fd(), EPOLLOUT, token);
+            false
+        }
+        Err(_) => true,
+    }
+}
+
+fn close_connection(epoll: &Epoll, connections: &mut HashMap<u64, Connection>, token: u64) {
+    if let Some(conn) = connections.remove(&token) {
+        epoll.remove(conn.stream.as_raw_fd());
+    }
+}
+
+/// Reads into `buf` until a full request line (ending in `\n`) is present,
+/// returning `Ok(None)` if more bytes are still needed.
+fn fill_request_line(stream: &mut TcpStream, buf: &mut Vec<u8>) -> io::Result<Option<String>> {
+    let mut scratch = [0u8; 512];
+    loop {
+        if let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+            let line = String::from_utf8_lossy(&buf[..pos]).trim_end_matches('\r').to_string();
+            return Ok(Some(line));
+        }
+
+        match stream.read(&mut scratch) {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "peer closed")),
+            Ok(n) => buf.extend_from_slice(&scratch[..n]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Submits the prime-counting workload to the CPU pool for either `/cpu`
+/// (empty `body_prefix`) or the CPU branch of `/mixed`; the worker sends its
+/// result back over `result_tx` and writes a byte to `waker_write` so the
+/// reactor wakes up and picks it off the channel. Returns `false` if the
+/// pool's queue was full and the job was not submitted.
+fn spawn_cpu_job(ctx: &mut ReactorCtx, token: u64, body_prefix: &'static str) -> bool {
+    let result_tx = ctx.result_tx.clone();
+    let waker_write = ctx.waker_write;
+    let submitted = ctx.cpu_pool.execute(move || {
+        let mut primes = 0usize;
+        for num in 2..10000 {
+            if crate::is_prime(num) {
+                primes += 1;
+            }
+        }
+        let body = format!("{body_prefix}Found {primes} primes up to 10,000");
+        let _ = result_tx.send(CpuResult { token, body });
+        let wake = [1u8];
+        unsafe {
+            write(waker_write, wake.as_ptr(), wake.len());
+        }
+    });
+
+    if submitted.is_err() {
+        eprintln!("CPU pool queue full; dropping /cpu request for token {token}");
+        return false;
+    }
+    true
+}
+
+/// Routes every request line that isn't `/cpu`, `/sleep`, or `/mixed` (those
+/// are handled directly in `read_ready`) to a status line and body.
+fn route(request_line: &str) -> (&'static str, String) {
+    if request_line.starts_with("GET / ") {
+        match std::fs::read_to_string("response.html") {
+            Ok(contents) => ("HTTP/1.1 200 OK", contents),
+            Err(_) => ("HTTP/1.1 500 Internal Server Error", "Failed to read response.html".to_string()),
+        }
+    } else {
+        let contents = std::fs::read_to_string("404.html").unwrap_or_else(|_| "404 Not Found".to_string());
+        ("HTTP/1.1 404 NOT FOUND", contents)
+    }
+}
+
+fn response_bytes(status_line: &str, body: &str) -> Vec<u8> {
+    format!("{status_line}\r\nContent-Length: {}\r\n\r\n{body}", body.len()).into_bytes()
+}